@@ -0,0 +1,312 @@
+//! Derive macro that generates the `ApiCall` prefix/argument-formatting
+//! boilerplate, the `HasArguments` impls, and the `ApiParameters` /
+//! `DataParameters` / `SearchParameters` marker impls that the `quandl`
+//! crate otherwise hand-writes once per query type.
+//!
+//! A query type need only annotate its struct:
+//!
+//! ```ignore
+//! #[derive(QuandlQuery)]
+//! #[quandl(prefix = "/datasets/{database_code}/{dataset_code}/data.json", args(api, data))]
+//! pub struct DataQuery {
+//!     database_code: String,
+//!     dataset_code: String,
+//!     data_arguments: DataArguments,
+//!     request_arguments: ApiArguments,
+//! }
+//! ```
+//!
+//! `prefix` is a format string whose `{field}` placeholders are filled in
+//! from the struct's own fields. `args` lists which parameter kinds
+//! (`api`, `data`, `search`) the generated `fmt_arguments` should combine,
+//! in the same `arg_1`/`arg_2`-style `&`-joining the hand-written impls use.
+//! Fields whose type is `ApiArguments`, `DataArguments` or
+//! `SearchArguments` are picked up automatically, whatever their name, and
+//! given `HasArguments` impls via the deriving crate's `crate::impl_has!`
+//! macro.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, DeriveInput, Data, Fields, Lit, Meta, NestedMeta};
+
+#[proc_macro_derive(QuandlQuery, attributes(quandl))]
+pub fn derive_quandl_query(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+
+    let (prefix, arg_kinds) = parse_quandl_attribute(&input);
+    let has_arguments = generate_has_arguments(&input);
+    let marker_impls = generate_marker_impls(name, &arg_kinds);
+    let fmt_arguments_body = generate_fmt_arguments(&arg_kinds);
+    let fmt_prefix_body = generate_fmt_prefix(&prefix, &input);
+
+    let expanded = quote! {
+        impl #name {
+            fn fmt_prefix(&self) -> Option<String> {
+                #fmt_prefix_body
+            }
+
+            fn fmt_arguments(&self) -> Option<String> {
+                #fmt_arguments_body
+            }
+        }
+
+        #marker_impls
+        #has_arguments
+    };
+
+    expanded.into()
+}
+
+/// Pull the `prefix` format string and `args(...)` list out of the struct's
+/// `#[quandl(...)]` attribute.
+fn parse_quandl_attribute(input: &DeriveInput) -> (String, Vec<String>) {
+    let mut prefix = String::new();
+    let mut arg_kinds = Vec::new();
+
+    for attr in &input.attrs {
+        if !attr.path.is_ident("quandl") {
+            continue;
+        }
+
+        let meta = attr.parse_meta().expect("malformed #[quandl(...)] attribute");
+
+        if let Meta::List(list) = meta {
+            for nested in list.nested {
+                match nested {
+                    NestedMeta::Meta(Meta::NameValue(name_value)) if name_value.path.is_ident("prefix") => {
+                        if let Lit::Str(lit) = name_value.lit {
+                            prefix = lit.value();
+                        }
+                    },
+
+                    NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("args") => {
+                        for kind in list.nested {
+                            if let NestedMeta::Meta(Meta::Path(path)) = kind {
+                                if let Some(ident) = path.get_ident() {
+                                    arg_kinds.push(ident.to_string());
+                                }
+                            }
+                        }
+                    },
+
+                    _ => {},
+                }
+            }
+        }
+    }
+
+    (prefix, arg_kinds)
+}
+
+/// Turn a `"/datasets/{database_code}/{dataset_code}/data.json"`-style
+/// prefix into the `format!(...)` call the hand-written impls use.
+fn generate_fmt_prefix(prefix: &str, input: &DeriveInput) -> proc_macro2::TokenStream {
+    let field_names = struct_field_names(input);
+    let mut format_str = String::new();
+    let mut field_args = Vec::new();
+
+    let mut chars = prefix.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '{' {
+            let mut field = String::new();
+
+            while let Some(&next) = chars.peek() {
+                if next == '}' {
+                    chars.next();
+                    break;
+                }
+
+                field.push(next);
+                chars.next();
+            }
+
+            if field_names.iter().any(|name| name == &field) {
+                format_str.push_str("{}");
+                let ident = syn::Ident::new(&field, proc_macro2::Span::call_site());
+                field_args.push(quote! { self.#ident });
+            }
+        } else {
+            format_str.push(c);
+        }
+    }
+
+    quote! { Some(format!(#format_str, #(#field_args),*)) }
+}
+
+fn struct_field_names(input: &DeriveInput) -> Vec<String> {
+    match &input.data {
+        Data::Struct(data) => {
+            match &data.fields {
+                Fields::Named(fields) => {
+                    fields.named.iter()
+                        .filter_map(|f| f.ident.as_ref().map(|i| i.to_string()))
+                        .collect()
+                },
+
+                _ => Vec::new(),
+            }
+        },
+
+        _ => Vec::new(),
+    }
+}
+
+/// Combine the requested parameter kinds the same `arg_1`/`arg_2`-style
+/// `&`-joining the hand-written `fmt_arguments` impls use.
+fn generate_fmt_arguments(arg_kinds: &[String]) -> proc_macro2::TokenStream {
+    let calls: Vec<_> = arg_kinds.iter().map(|kind| {
+        match kind.as_str() {
+            "api" => quote! { crate::parameters::ApiParameters::fmt(self) },
+            "data" => quote! { crate::parameters::DataParameters::fmt(self) },
+            "search" => quote! { crate::parameters::SearchParameters::fmt(self) },
+            other => panic!("unknown #[quandl(args(...))] kind `{}`", other),
+        }
+    }).collect();
+
+    quote! {
+        let parts: Vec<String> = [#(#calls),*].iter().filter_map(|p: &Option<String>| p.clone()).collect();
+
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join("&"))
+        }
+    }
+}
+
+fn generate_marker_impls(name: &syn::Ident, arg_kinds: &[String]) -> proc_macro2::TokenStream {
+    let impls: Vec<_> = arg_kinds.iter().map(|kind| {
+        match kind.as_str() {
+            "api" => quote! { impl crate::parameters::ApiParameters for #name {} },
+            "data" => quote! { impl crate::parameters::DataParameters for #name {} },
+            "search" => quote! { impl crate::parameters::SearchParameters for #name {} },
+            other => panic!("unknown #[quandl(args(...))] kind `{}`", other),
+        }
+    }).collect();
+
+    quote! { #(#impls)* }
+}
+
+/// Emit `crate::impl_has!` calls for every field whose type is one of the
+/// crate's argument bundles, so a query type gets `HasArguments` for free.
+fn generate_has_arguments(input: &DeriveInput) -> proc_macro2::TokenStream {
+    let name = &input.ident;
+
+    let fields = match &input.data {
+        Data::Struct(data) => {
+            match &data.fields {
+                Fields::Named(fields) => &fields.named,
+                _ => return quote! {},
+            }
+        },
+
+        _ => return quote! {},
+    };
+
+    let impls: Vec<_> = fields.iter().filter_map(|field| {
+        let ident = field.ident.as_ref()?;
+        let type_name = type_name(&field.ty)?;
+
+        match type_name.as_str() {
+            "ApiArguments" | "DataArguments" | "SearchArguments" => {
+                let ty = &field.ty;
+                Some(quote! { crate::impl_has!(#name, #ty, #ident); })
+            },
+
+            _ => None,
+        }
+    }).collect();
+
+    quote! { #(#impls)* }
+}
+
+fn type_name(ty: &syn::Type) -> Option<String> {
+    match ty {
+        syn::Type::Path(path) => path.path.segments.last().map(|s| s.ident.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn parses_prefix_and_args_from_the_quandl_attribute() {
+        let input: DeriveInput = parse_quote! {
+            #[quandl(prefix = "/databases/{database_code}.json", args(api))]
+            pub struct DatabaseMetadataQuery {
+                database_code: String,
+                request_arguments: ApiArguments,
+            }
+        };
+
+        let (prefix, arg_kinds) = parse_quandl_attribute(&input);
+
+        assert_eq!(prefix, "/databases/{database_code}.json");
+        assert_eq!(arg_kinds, vec!["api".to_string()]);
+    }
+
+    #[test]
+    fn parses_multiple_arg_kinds() {
+        let input: DeriveInput = parse_quote! {
+            #[quandl(prefix = "/datasets/{database_code}/{dataset_code}/data.json", args(api, data))]
+            pub struct DataQuery {
+                database_code: String,
+                dataset_code: String,
+                data_arguments: DataArguments,
+                request_arguments: ApiArguments,
+            }
+        };
+
+        let (_, arg_kinds) = parse_quandl_attribute(&input);
+
+        assert_eq!(arg_kinds, vec!["api".to_string(), "data".to_string()]);
+    }
+
+    #[test]
+    fn collects_named_struct_field_names() {
+        let input: DeriveInput = parse_quote! {
+            pub struct DatasetMetadataQuery {
+                database_code: String,
+                dataset_code: String,
+                request_arguments: ApiArguments,
+            }
+        };
+
+        let names = struct_field_names(&input);
+
+        assert_eq!(names, vec!["database_code", "dataset_code", "request_arguments"]);
+    }
+
+    #[test]
+    fn identifies_argument_bundle_fields_by_type_name() {
+        let input: DeriveInput = parse_quote! {
+            pub struct DataQuery {
+                database_code: String,
+                data_arguments: DataArguments,
+                request_arguments: ApiArguments,
+            }
+        };
+
+        let fields = match &input.data {
+            Data::Struct(data) => match &data.fields {
+                Fields::Named(fields) => &fields.named,
+                _ => panic!("expected named fields"),
+            },
+            _ => panic!("expected a struct"),
+        };
+
+        let argument_fields: Vec<_> = fields.iter()
+            .filter_map(|f| type_name(&f.ty))
+            .filter(|name| name == "DataArguments" || name == "ApiArguments")
+            .collect();
+
+        assert_eq!(argument_fields, vec!["DataArguments", "ApiArguments"]);
+    }
+}
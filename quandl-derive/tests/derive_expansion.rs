@@ -0,0 +1,62 @@
+//! Expands `#[derive(QuandlQuery)]` against a small stand-in for the
+//! `quandl` crate's `parameters` module, so a regression like generating
+//! `::quandl::...` paths (which only resolve for consumers literally named
+//! `quandl`) fails to compile instead of silently passing unit tests that
+//! never expand the macro at all.
+
+use quandl_derive::QuandlQuery;
+
+mod parameters {
+    #[derive(Debug, Clone, Copy, PartialEq, Default)]
+    pub struct ApiArguments {
+        pub page: Option<u64>,
+    }
+
+    pub trait HasArguments<T> {
+        fn arguments(&self) -> &T;
+        fn arguments_mut(&mut self) -> &mut T;
+    }
+
+    #[macro_export]
+    macro_rules! impl_has {
+        ($ty:ty, $arg_ty:ty, $field:ident) => {
+            impl $crate::parameters::HasArguments<$arg_ty> for $ty {
+                fn arguments(&self) -> &$arg_ty {
+                    &self.$field
+                }
+
+                fn arguments_mut(&mut self) -> &mut $arg_ty {
+                    &mut self.$field
+                }
+            }
+        };
+    }
+
+    pub trait ApiParameters: HasArguments<ApiArguments> {
+        fn fmt(&self) -> Option<String> {
+            self.arguments().page.map(|page| format!("page={}", page))
+        }
+    }
+}
+
+#[derive(QuandlQuery)]
+#[quandl(prefix = "/databases/{database_code}.json", args(api))]
+struct DatabaseMetadataQuery {
+    database_code: String,
+    request_arguments: parameters::ApiArguments,
+}
+
+#[test]
+fn derived_prefix_and_arguments_match_the_struct() {
+    use parameters::HasArguments;
+
+    let mut query = DatabaseMetadataQuery {
+        database_code: "WIKI".to_string(),
+        request_arguments: parameters::ApiArguments { page: Some(1) },
+    };
+
+    query.arguments_mut().page = Some(2);
+
+    assert_eq!(query.fmt_prefix(), Some("/databases/WIKI.json".to_string()));
+    assert_eq!(query.fmt_arguments(), Some("page=2".to_string()));
+}
@@ -0,0 +1,130 @@
+use query::ApiVersion;
+
+/// Arguments shared by every API call: pagination and the response envelope
+/// version the caller expects to decode against.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ApiArguments {
+    pub page: Option<u64>,
+    pub per_page: Option<u64>,
+    pub api_version: ApiVersion,
+}
+
+impl ApiArguments {
+    /// Opt this query into a non-default response envelope, e.g.
+    /// `ApiVersion::V2023Flat` once Quandl/Nasdaq Data Link ships it.
+    ///
+    pub fn set_api_version(&mut self, version: ApiVersion) {
+        self.api_version = version;
+    }
+}
+
+/// Arguments specific to a data query: the date range, collapse frequency
+/// and sort order Quandl accepts on `/data.json` endpoints.
+///
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DataArguments {
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+    pub collapse: Option<String>,
+    pub order: Option<String>,
+}
+
+/// Arguments specific to a search query.
+///
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SearchArguments {
+    pub query: Option<String>,
+}
+
+/// Gives a query type access to one of its argument bundles by reference,
+/// generated per-field by [`impl_has!`].
+///
+pub trait HasArguments<T> {
+    fn arguments(&self) -> &T;
+    fn arguments_mut(&mut self) -> &mut T;
+}
+
+/// Generate a [`HasArguments`] impl for `$ty`'s `$field`, typed `$arg_ty`.
+///
+#[macro_export]
+macro_rules! impl_has {
+    ($ty:ty, $arg_ty:ty, $field:ident) => {
+        impl $crate::parameters::HasArguments<$arg_ty> for $ty {
+            fn arguments(&self) -> &$arg_ty {
+                &self.$field
+            }
+
+            fn arguments_mut(&mut self) -> &mut $arg_ty {
+                &mut self.$field
+            }
+        }
+    };
+}
+
+/// Formats a query's [`ApiArguments`] (`page`/`per_page`) into the
+/// `&`-joined querystring fragment `ApiCall::fmt_arguments` relies on.
+///
+pub trait ApiParameters: HasArguments<ApiArguments> {
+    fn fmt(&self) -> Option<String> {
+        let arguments = self.arguments();
+        let mut parts = Vec::new();
+
+        if let Some(page) = arguments.page {
+            parts.push(format!("page={}", page));
+        }
+
+        if let Some(per_page) = arguments.per_page {
+            parts.push(format!("per_page={}", per_page));
+        }
+
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join("&"))
+        }
+    }
+}
+
+/// Formats a query's [`DataArguments`] into the `&`-joined querystring
+/// fragment `ApiCall::fmt_arguments` relies on.
+///
+pub trait DataParameters: HasArguments<DataArguments> {
+    fn fmt(&self) -> Option<String> {
+        let arguments = self.arguments();
+        let mut parts = Vec::new();
+
+        if let Some(ref start_date) = arguments.start_date {
+            parts.push(format!("start_date={}", start_date));
+        }
+
+        if let Some(ref end_date) = arguments.end_date {
+            parts.push(format!("end_date={}", end_date));
+        }
+
+        if let Some(ref collapse) = arguments.collapse {
+            parts.push(format!("collapse={}", collapse));
+        }
+
+        if let Some(ref order) = arguments.order {
+            parts.push(format!("order={}", order));
+        }
+
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join("&"))
+        }
+    }
+}
+
+/// Formats a query's [`SearchArguments`] into the `&`-joined querystring
+/// fragment `ApiCall::fmt_arguments` relies on.
+///
+pub trait SearchParameters: HasArguments<SearchArguments> {
+    fn fmt(&self) -> Option<String> {
+        let arguments = self.arguments();
+
+        arguments.query.as_ref().map(|query| format!("query={}", query))
+    }
+}